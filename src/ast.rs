@@ -0,0 +1,204 @@
+/// A byte-offset range into the original pattern string, used to point
+/// diagnostics (and eventually tooling) at the exact offending text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+/// One item inside a `[...]` character class: either a single char or an
+/// inclusive `a-z` style range.
+#[derive(Debug, Clone)]
+pub enum ClassItem {
+    Char(char),
+    Range(char, char),
+}
+
+/// A parsed `[abc]`, `[a-z]`, or negated `[^...]` character class.
+#[derive(Debug, Clone)]
+pub struct ClassSpec {
+    pub negated: bool,
+    pub items: Vec<ClassItem>,
+}
+
+impl ClassSpec {
+    pub fn matches(&self, c: char) -> bool {
+        let hit = self.items.iter().any(|item| match item {
+            ClassItem::Char(x) => *x == c,
+            ClassItem::Range(lo, hi) => *lo <= c && c <= *hi,
+        });
+        hit != self.negated
+    }
+}
+
+/// The parsed structure of a pattern, independent of how it gets executed.
+/// `Parser` builds this; `ast_to_nfa` lowers it to an `Nfa`.
+#[derive(Debug, Clone)]
+pub enum Ast {
+    Char(char, Span),
+    Any(Span),
+    Class(ClassSpec, Span),
+    /// The empty match, used when desugaring `x?` to `x | Empty`.
+    Empty(Span),
+    /// `^`: asserts the current position is the start of the text.
+    StartAnchor(Span),
+    /// `$`: asserts the current position is the end of the text.
+    EndAnchor(Span),
+    /// A placeholder left by the error-recovering parser in place of text it
+    /// couldn't make sense of. Matches the empty string so a tree containing
+    /// one can still be lowered, though the strict entry points reject any
+    /// `Ast` that contains one.
+    Error(Span),
+    Concat(Vec<Ast>, Span),
+    Alt(Box<Ast>, Box<Ast>, Span),
+    Star(Box<Ast>, Span),
+    /// A parenthesized group, numbered left-to-right by opening paren
+    /// starting at 1 (group 0 is reserved for the whole match).
+    Group(Box<Ast>, usize, Span),
+}
+
+impl Ast {
+    pub fn span(&self) -> Span {
+        match self {
+            Ast::Char(_, span) => *span,
+            Ast::Any(span) => *span,
+            Ast::Class(_, span) => *span,
+            Ast::Empty(span) => *span,
+            Ast::StartAnchor(span) => *span,
+            Ast::EndAnchor(span) => *span,
+            Ast::Error(span) => *span,
+            Ast::Concat(_, span) => *span,
+            Ast::Alt(_, _, span) => *span,
+            Ast::Star(_, span) => *span,
+            Ast::Group(_, _, span) => *span,
+        }
+    }
+
+    /// The highest capture group number appearing anywhere in this tree, or
+    /// 0 if there are no groups. Used to size the VM's capture slot array.
+    pub fn max_group(&self) -> usize {
+        match self {
+            Ast::Char(..) | Ast::Any(_) | Ast::Class(..) | Ast::Empty(_) => 0,
+            Ast::StartAnchor(_) | Ast::EndAnchor(_) => 0,
+            Ast::Error(_) => 0,
+            Ast::Concat(parts, _) => parts.iter().map(Ast::max_group).max().unwrap_or(0),
+            Ast::Alt(l, r, _) => l.max_group().max(r.max_group()),
+            Ast::Star(inner, _) => inner.max_group(),
+            Ast::Group(inner, idx, _) => (*idx).max(inner.max_group()),
+        }
+    }
+
+    /// Checks that every `^`/`$` in this tree is somewhere `Nfa`/`Dfa`
+    /// simulation (which only ever checks whole-string acceptance, so it
+    /// can't tell a mid-string position from start/end-of-text) can still
+    /// honor correctly: `^` only as the leading atom of the pattern, `$`
+    /// only as the trailing one. Nested inside a `Star` is always rejected,
+    /// since a repeated body isn't at the start/end on every iteration.
+    /// The position-aware VM (`vm::search`/`vm::captures`) doesn't need
+    /// this restriction, so it's only enforced on the `Nfa`/`Dfa` entry
+    /// points (`regex_to_nfa`, `dfa::compile`).
+    pub fn check_anchor_positions(&self) -> Result<(), ParseError> {
+        fn check(ast: &Ast, at_start: bool, at_end: bool) -> Result<(), ParseError> {
+            match ast {
+                Ast::StartAnchor(span) => {
+                    if at_start {
+                        Ok(())
+                    } else {
+                        Err(ParseError::new(
+                            *span,
+                            "'^' is only supported at the start of the pattern",
+                        ))
+                    }
+                }
+                Ast::EndAnchor(span) => {
+                    if at_end {
+                        Ok(())
+                    } else {
+                        Err(ParseError::new(
+                            *span,
+                            "'$' is only supported at the end of the pattern",
+                        ))
+                    }
+                }
+                Ast::Char(..) | Ast::Any(_) | Ast::Class(..) | Ast::Empty(_) | Ast::Error(_) => {
+                    Ok(())
+                }
+                Ast::Concat(parts, _) => {
+                    let last = parts.len().saturating_sub(1);
+                    for (i, part) in parts.iter().enumerate() {
+                        check(part, at_start && i == 0, at_end && i == last)?;
+                    }
+                    Ok(())
+                }
+                Ast::Alt(l, r, _) => {
+                    check(l, at_start, at_end)?;
+                    check(r, at_start, at_end)
+                }
+                Ast::Star(inner, _) => check(inner, false, false),
+                Ast::Group(inner, _, _) => check(inner, at_start, at_end),
+            }
+        }
+        check(self, true, true)
+    }
+
+    /// Rejects `[...]` ranges wide enough to blow up `Nfa::to_dfa`'s
+    /// per-codepoint alphabet (and the subset-construction work that
+    /// follows it) — e.g. `[\u{0}-\u{10FFFF}]` hangs/OOMs on a single,
+    /// syntactically ordinary pattern otherwise. Real-world classes
+    /// (`a-z`, `0-9`, ...) are nowhere near this limit, so rejecting wider
+    /// ones is a correctness guard on `dfa::compile`'s public contract, not
+    /// a meaningful feature restriction.
+    pub fn check_class_ranges(&self) -> Result<(), ParseError> {
+        const MAX_CLASS_RANGE: u32 = 4096;
+
+        match self {
+            Ast::Class(spec, span) => {
+                for item in &spec.items {
+                    if let ClassItem::Range(lo, hi) = item {
+                        if (*hi as u32).saturating_sub(*lo as u32) >= MAX_CLASS_RANGE {
+                            return Err(ParseError::new(
+                                *span,
+                                format!(
+                                    "character class range is too wide (max {MAX_CLASS_RANGE} codepoints)"
+                                ),
+                            ));
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Ast::Char(..) | Ast::Any(_) | Ast::Empty(_) | Ast::StartAnchor(_) | Ast::EndAnchor(_) | Ast::Error(_) => {
+                Ok(())
+            }
+            Ast::Concat(parts, _) => parts.iter().try_for_each(Ast::check_class_ranges),
+            Ast::Alt(l, r, _) => {
+                l.check_class_ranges()?;
+                r.check_class_ranges()
+            }
+            Ast::Star(inner, _) => inner.check_class_ranges(),
+            Ast::Group(inner, _, _) => inner.check_class_ranges(),
+        }
+    }
+}
+
+/// A parse failure, pointing at the `Span` of the offending text.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::ast::ClassItem;
+use crate::nfa::{Matcher, Nfa, State, StateSet};
+
+type DfaState = usize;
+
+/// A DFA obtained from an `Nfa` via subset construction. Unlike `Nfa::simulate`,
+/// matching an input is a table walk with no per-step set allocation, which
+/// pays off when the same compiled pattern is matched against many inputs.
+///
+/// `.`/`[^...]` match arbitrarily many chars, so the alphabet used to build
+/// `transitions` is the finite set of chars the pattern mentions explicitly
+/// plus one `default_char` standing in for "everything else" — every char
+/// not mentioned explicitly behaves identically with respect to this
+/// pattern's matchers, so one representative is enough.
+#[derive(Debug)]
+pub struct Dfa {
+    start: DfaState,
+    accept: HashSet<DfaState>,
+    transitions: HashMap<(DfaState, char), DfaState>,
+    default_char: char,
+}
+
+impl Dfa {
+    pub fn simulate(&self, input: &str) -> bool {
+        let mut state = self.start;
+
+        for c in input.chars() {
+            let lookup = if self.transitions.contains_key(&(state, c)) {
+                c
+            } else {
+                self.default_char
+            };
+            match self.transitions.get(&(state, lookup)) {
+                Some(&next) => state = next,
+                None => return false,
+            }
+        }
+
+        self.accept.contains(&state)
+    }
+}
+
+impl Nfa {
+    pub fn to_dfa(&self) -> Dfa {
+        let mut alphabet = self.alphabet();
+        let default_char = pick_default_char(&alphabet);
+        alphabet.insert(default_char);
+
+        let start_set: BTreeSet<State> = self.eps_closure(HashSet::from([self.start])).into_iter().collect();
+
+        let mut ids: HashMap<BTreeSet<State>, DfaState> = HashMap::new();
+        ids.insert(start_set.clone(), 0);
+        let mut worklist = vec![start_set];
+        let mut transitions = HashMap::new();
+
+        while let Some(set) = worklist.pop() {
+            let from_id = ids[&set];
+            let as_hashset: StateSet = set.iter().copied().collect();
+
+            for &c in &alphabet {
+                // An empty move still gets a transition (to the empty, never
+                // accepting set), so `simulate` only falls back to
+                // `default_char` for chars outside the alphabet.
+                let moved = self.get_move(&as_hashset, c);
+                let closure: BTreeSet<State> = self.eps_closure(moved).into_iter().collect();
+
+                let to_id = match ids.get(&closure) {
+                    Some(&id) => id,
+                    None => {
+                        let id = ids.len();
+                        ids.insert(closure.clone(), id);
+                        worklist.push(closure);
+                        id
+                    }
+                };
+
+                transitions.insert((from_id, c), to_id);
+            }
+        }
+
+        let accept = ids
+            .iter()
+            .filter(|(set, _)| set.contains(&self.accept))
+            .map(|(_, &id)| id)
+            .collect();
+
+        Dfa {
+            start: 0,
+            accept,
+            transitions,
+            default_char,
+        }
+    }
+
+    /// Every char this pattern mentions literally: as a plain `Char`, inside
+    /// a class item, or as a range endpoint. Ranges are expanded fully, which
+    /// is only safe because `regex_to_nfa` rejects overly wide ones first
+    /// (see `Ast::check_class_ranges`) — this method has no span to report
+    /// an error against, so it assumes that guard already ran.
+    fn alphabet(&self) -> HashSet<char> {
+        let mut alphabet = HashSet::new();
+        for pairs in self.transitions.values() {
+            for (label, _) in pairs {
+                match label {
+                    Some(Matcher::Char(c)) => {
+                        alphabet.insert(*c);
+                    }
+                    Some(Matcher::Class(spec)) => {
+                        for item in &spec.items {
+                            match item {
+                                ClassItem::Char(c) => {
+                                    alphabet.insert(*c);
+                                }
+                                ClassItem::Range(lo, hi) => {
+                                    alphabet.extend(*lo..=*hi);
+                                }
+                            }
+                        }
+                    }
+                    Some(Matcher::Any) | None => {}
+                }
+            }
+        }
+        alphabet
+    }
+}
+
+/// Picks a char outside `excluded` to stand in for "everything else" in the
+/// DFA's alphabet (see `Dfa`'s doc comment).
+fn pick_default_char(excluded: &HashSet<char>) -> char {
+    (0u32..)
+        .filter_map(char::from_u32)
+        .find(|c| !excluded.contains(c))
+        .expect("char space is not exhausted by a finite pattern alphabet")
+}
+
+pub fn compile(pattern: &str) -> Result<Dfa, crate::ast::ParseError> {
+    let nfa = crate::regex_to_nfa(pattern)?;
+    Ok(nfa.to_dfa())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_literal_concat() {
+        let dfa = compile("abc").unwrap();
+        assert!(dfa.simulate("abc"));
+        assert!(!dfa.simulate("ab"));
+        assert!(!dfa.simulate("abcd"));
+    }
+
+    #[test]
+    fn matches_alternation_and_star() {
+        let dfa = compile("(cat|dog)*").unwrap();
+        assert!(dfa.simulate(""));
+        assert!(dfa.simulate("catdog"));
+        assert!(dfa.simulate("dogdogcat"));
+        assert!(!dfa.simulate("cats"));
+    }
+
+    #[test]
+    fn matches_wildcard_and_class() {
+        let dfa = compile("a.c").unwrap();
+        assert!(dfa.simulate("abc"));
+        assert!(dfa.simulate("axc"));
+        assert!(!dfa.simulate("ac"));
+
+        let dfa = compile("[a-z]+").unwrap();
+        assert!(dfa.simulate("hello"));
+        assert!(!dfa.simulate("Hello"));
+    }
+
+    #[test]
+    fn rejects_overly_wide_class_range() {
+        assert!(compile("[a-z]").is_ok());
+        assert!(compile("[\u{0}-\u{10FFFF}]").is_err());
+    }
+
+    #[test]
+    fn agrees_with_nfa_simulate() {
+        // Subset construction should accept exactly what the Nfa it was
+        // built from accepts.
+        for pattern in ["a(b|c)*d", "[a-z]+", "x?y"] {
+            let nfa = crate::regex_to_nfa(pattern).unwrap();
+            let dfa = compile(pattern).unwrap();
+            for input in ["", "a", "abcd", "xy", "y", "zzz"] {
+                assert_eq!(
+                    nfa.simulate(input),
+                    dfa.simulate(input),
+                    "pattern {pattern:?}, input {input:?}"
+                );
+            }
+        }
+    }
+}
@@ -1,236 +1,73 @@
-use std::collections::HashMap;
-use std::collections::HashSet;
-use std::env;
-
-type Label = Option<char>;
-type State = usize;
-type StateSet = HashSet<State>;
-type Transitions = HashMap<State, Vec<(Label, State)>>;
-
-#[derive(Debug)]
-pub struct Nfa {
-    start: State,
-    accept: State,
-    transitions: Transitions,
-}
-
-impl Nfa {
-    fn new(start: State, accept: State, transitions: Transitions) -> Self {
-        Self {
-            start,
-            accept,
-            transitions,
-        }
-    }
-
-    fn add_transition(&mut self, from: State, label: Label, to: State) {
-        self.transitions.entry(from).or_default().push((label, to));
-    }
+mod ast;
+mod dfa;
+mod nfa;
+mod parser;
+mod vm;
 
-    fn eps_closure(&self, states: StateSet) -> StateSet {
-
-        let mut ec = states;
-        let mut stack: Vec<State> = ec.clone().into_iter().collect();
-
-        while let Some(state) = stack.pop() {
-            if let Some(pairs) = self.transitions.get(&state) {
-                for (label, s) in pairs.iter() {
-                    if label.is_none() && ec.insert(*s) {
-                        stack.push(*s);
-                    }
-                }
-            }
-        }
-        ec
-    }
+use std::env;
 
-    fn get_move(&self, states: &StateSet, symbol: char) -> StateSet {
-
-        let mut res = StateSet::new();
-
-        for from in states.iter() {
-            if let Some(pairs) = self.transitions.get(from) {
-                for (label, to) in pairs.iter() {
-                    if let Some(c) = label {
-                        if *c == symbol {
-                            res.insert(*to);
-                        }
-                    }
-                }
-            }
-        }
-        res
-    }
+pub use ast::{Ast, ParseError, Span};
+pub use dfa::{compile, Dfa};
+pub use nfa::Nfa;
+pub use vm::{Captures, FindIter};
 
-    fn simulate(&self, input: &str) -> bool {
-        // From figure 3.37 of dragon book
-        let s0 = HashSet::from([self.start]);
-        let mut states = self.eps_closure(s0);
+use nfa::ast_to_nfa;
 
-        for c in input.chars() {
-            let from_move = self.get_move(&states, c);
-            states = self.eps_closure(from_move);
-        }
+/// Parses `pattern` with recovery, collecting every problem instead of
+/// stopping at the first one. See `parser::parse` for the recovery rules.
+pub fn parse(pattern: &str) -> (Option<Ast>, Vec<ParseError>) {
+    parser::parse(pattern)
+}
 
-        return states.contains(&self.accept);
+/// Strict, all-or-nothing parse: any diagnostic from `parse` is treated as
+/// failure, reporting the first one found.
+pub fn regex_to_ast(pattern: &str) -> Result<Ast, ParseError> {
+    let (ast, mut errors) = parser::parse(pattern);
+    if !errors.is_empty() {
+        return Err(errors.remove(0));
     }
+    Ok(ast.expect("parser::parse always returns Some"))
 }
 
-struct Parser<'a> {
-    s: &'a str, // pattern
-    i: usize, // index
-    next_state: State,
+/// Lowers `pattern` to an `Nfa`, rejecting `^`/`$` in a position plain
+/// `Nfa`/`Dfa` simulation can't honor correctly (see
+/// `Ast::check_anchor_positions`) and character classes wide enough to blow
+/// up `Dfa` construction (see `Ast::check_class_ranges`).
+pub fn regex_to_nfa(pattern: &str) -> Result<Nfa, ParseError> {
+    let ast = regex_to_ast(pattern)?;
+    ast.check_anchor_positions()?;
+    ast.check_class_ranges()?;
+    Ok(ast_to_nfa(&ast))
 }
 
-impl<'a> Parser<'a> {
-    fn new(input: &'a str) -> Self {
-        Self {
-            s: input,
-            i: 0,
-            next_state: 0,
-        }   
-    }
-
-    fn fresh(&mut self) -> State {
-        let state = self.next_state;
-        self.next_state += 1;
-        state
-    }
-
-    fn peek(&self) -> Label {
-        self.s[self.i..].chars().next()
-    }
-
-    fn eat(&mut self, expected: char) -> bool {
-        if self.peek() == Some(expected) {
-            self.consume();
-            true
-        } else {
-            false
-        }
-    }
-
-    fn consume(&mut self) -> Label {
-        let c = self.peek()?;
-        self.i += c.len_utf8();
-        Some(c)
-    }
-
-    fn parse_regex(&mut self) -> Result<Nfa, String> {
-        self.parse_alt()
-    }
-
-    fn parse_alt(&mut self) -> Result<Nfa, String> {
-        let mut left = self.parse_concat()?;
-        while self.eat('|') {
-            let right = self.parse_concat()?;
-            left = self.build_alt(left, right);
-        }
-        Ok(left)
-    }
-
-    fn parse_concat(&mut self) -> Result<Nfa, String> {
-
-        let mut res: Option<Nfa> = None;
-
-        while let Some(c) = self.peek() {
-            if c == ')' || c == '|' {
-                break;
-            }
-            let rep = self.parse_rep()?;
-            res = match res {
-                Some(nfa) => Some(self.build_concat(nfa, rep)),
-                None => Some(rep),
-            }
-        }
-
-        match res {
-            None => Err("Expected expression".into()),
-            Some(nfa) => Ok(nfa),
-        }
-    }
-
-    fn parse_rep(&mut self) -> Result<Nfa, String> {
-        let mut nfa = self.parse_primary()?;
-        if self.eat('*') {
-            nfa = self.build_star(nfa);
-        }
-        Ok(nfa)
-    }
-
-    fn parse_primary(&mut self) -> Result<Nfa, String> {
-        match self.peek() {
-            Some('(') => {
-                self.consume();
-                let nfa = self.parse_regex()?;
-                if !self.eat(')') {
-                    return Err("Expected ')'".into());
-                }
-                Ok(nfa)
-            }
-            Some('*') | Some('|') | Some(')') | None => {
-                Err("Unexpected token".into())
-            }
-            Some(c) => {
-                self.consume();
-                Ok(self.build_char(c))
-            }
-        }
-    }
-
-    fn build_char(&mut self, c: char) -> Nfa {
-        let s = self.fresh();
-        let a = self.fresh();
-        let mut nfa = Nfa::new(s, a, Transitions::new());
-        nfa.add_transition(s, Some(c), a);
-        nfa
-    }
-
-    fn build_concat(&mut self, left: Nfa, right: Nfa) -> Nfa {
-        let mut transitions = left.transitions;
-        transitions.extend(right.transitions);
-        let mut nfa = Nfa::new(left. start, right.accept, transitions);
-        nfa.add_transition(left.accept, None, right.start);
-        nfa
-    }
-
-    fn build_alt(&mut self, left: Nfa, right: Nfa) -> Nfa {
-        let s = self.fresh();
-        let a = self.fresh();
-        let mut transitions = left.transitions;
-        transitions.extend(right.transitions);
-        let mut nfa = Nfa::new(s, a, transitions);
-        nfa.add_transition(s, None, left.start);
-        nfa.add_transition(s, None, right.start);
-        nfa.add_transition(left.accept, None, a);
-        nfa.add_transition(right.accept, None, a);
-        nfa
-    }
+pub fn regex_match(pattern: &str, input: &str) -> bool {
+    let nfa = regex_to_nfa(pattern).unwrap();
+    nfa.simulate(input)
+}
 
-    fn build_star(&mut self, inner: Nfa) -> Nfa {
-        let s = self.fresh();
-        let a = self.fresh();
-        let mut nfa = Nfa::new(s, a, inner.transitions);
-        nfa.add_transition(s, None, inner.start);
-        nfa.add_transition(s, None, a);
-        nfa.add_transition(inner.accept, None, inner.start);
-        nfa.add_transition(inner.accept, None, a);
-        nfa
-    }
+/// Like `regex_match`, but also reports where each capture group matched.
+/// Group 0 is the whole match; `None` for a group means it never
+/// participated (e.g. the untaken side of an `|`). Fails with the `Parser`'s
+/// first diagnostic rather than panicking if `pattern` doesn't parse.
+pub fn captures(pattern: &str, input: &str) -> Result<Option<Captures>, ParseError> {
+    let ast = regex_to_ast(pattern)?;
+    Ok(vm::captures(&ast, input))
 }
 
-pub fn regex_to_nfa(pattern: &str) -> Result<Nfa, String> {
-    let mut p = Parser::new(pattern);
-    let nfa = p.parse_regex()?;
-    if p.peek().is_some() {
-        return Err("Unexpected trailing characters".into());
-    }
-    Ok(nfa)
+/// Finds the leftmost match of `pattern` anywhere in `input` (unanchored),
+/// returning its byte span. `^`/`$` still assert true start/end-of-text.
+/// Fails with the `Parser`'s first diagnostic rather than panicking if
+/// `pattern` doesn't parse.
+pub fn find(pattern: &str, input: &str) -> Result<Option<(usize, usize)>, ParseError> {
+    let ast = regex_to_ast(pattern)?;
+    Ok(vm::find(&ast, input))
 }
 
-pub fn regex_match(pattern: &str, input: &str) -> bool {
-    let nfa = regex_to_nfa(pattern).unwrap();
-    nfa.simulate(input)
+/// Like `find`, but yields every non-overlapping match in `input` in order.
+/// Fails the same way `find` does if `pattern` doesn't parse.
+pub fn find_iter<'a>(pattern: &str, input: &'a str) -> Result<FindIter<'a>, ParseError> {
+    let ast = regex_to_ast(pattern)?;
+    Ok(vm::find_iter(&ast, input))
 }
 
 fn main() {
@@ -250,3 +87,26 @@ fn main() {
         println!("{} doesn't match {}", input, pattern);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regex_to_nfa_rejects_mid_pattern_anchors() {
+        assert!(regex_to_nfa("a^b").is_err());
+        assert!(regex_to_nfa("(^a)*").is_err());
+        assert!(regex_to_nfa("^ab$").is_ok());
+    }
+
+    #[test]
+    fn captures_find_find_iter_report_parse_errors_instead_of_panicking() {
+        assert!(captures("a(b", "ab").is_err());
+        assert!(find("a(b", "ab").is_err());
+        assert!(find_iter("a(b", "ab").is_err());
+
+        assert!(captures("(a)(b)", "ab").is_ok());
+        assert!(find("ab", "xab").is_ok());
+        assert!(find_iter("ab", "xab").is_ok());
+    }
+}
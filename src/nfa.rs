@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::ast::{Ast, ClassSpec};
+
+/// What a non-epsilon transition accepts. Generalizes the original
+/// literal-char-only transitions so `.` and `[...]` can share the same
+/// machinery as plain character matches.
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    Char(char),
+    Any,
+    Class(ClassSpec),
+}
+
+impl Matcher {
+    pub(crate) fn matches(&self, c: char) -> bool {
+        match self {
+            Matcher::Char(expected) => *expected == c,
+            Matcher::Any => true,
+            Matcher::Class(spec) => spec.matches(c),
+        }
+    }
+}
+
+pub type Label = Option<Matcher>;
+pub type State = usize;
+pub type StateSet = HashSet<State>;
+pub type Transitions = HashMap<State, Vec<(Label, State)>>;
+
+#[derive(Debug)]
+pub struct Nfa {
+    pub(crate) start: State,
+    pub(crate) accept: State,
+    pub(crate) transitions: Transitions,
+}
+
+impl Nfa {
+    fn new(start: State, accept: State, transitions: Transitions) -> Self {
+        Self {
+            start,
+            accept,
+            transitions,
+        }
+    }
+
+    fn add_transition(&mut self, from: State, label: Label, to: State) {
+        self.transitions.entry(from).or_default().push((label, to));
+    }
+
+    pub(crate) fn eps_closure(&self, states: StateSet) -> StateSet {
+        let mut ec = states;
+        let mut stack: Vec<State> = ec.clone().into_iter().collect();
+
+        while let Some(state) = stack.pop() {
+            if let Some(pairs) = self.transitions.get(&state) {
+                for (label, s) in pairs.iter() {
+                    if label.is_none() && ec.insert(*s) {
+                        stack.push(*s);
+                    }
+                }
+            }
+        }
+        ec
+    }
+
+    pub(crate) fn get_move(&self, states: &StateSet, symbol: char) -> StateSet {
+        let mut res = StateSet::new();
+
+        for from in states.iter() {
+            if let Some(pairs) = self.transitions.get(from) {
+                for (label, to) in pairs.iter() {
+                    if let Some(matcher) = label {
+                        if matcher.matches(symbol) {
+                            res.insert(*to);
+                        }
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    pub fn simulate(&self, input: &str) -> bool {
+        // From figure 3.37 of dragon book
+        let s0 = HashSet::from([self.start]);
+        let mut states = self.eps_closure(s0);
+
+        for c in input.chars() {
+            let from_move = self.get_move(&states, c);
+            states = self.eps_closure(from_move);
+        }
+
+        states.contains(&self.accept)
+    }
+}
+
+/// Lowers a parsed `Ast` into an `Nfa` via the standard Thompson construction.
+/// Kept separate from `Parser` so parsing and tree-building can evolve
+/// independently (e.g. the desugaring passes added later).
+struct NfaBuilder {
+    next_state: State,
+}
+
+impl NfaBuilder {
+    fn new() -> Self {
+        Self { next_state: 0 }
+    }
+
+    fn fresh(&mut self) -> State {
+        let state = self.next_state;
+        self.next_state += 1;
+        state
+    }
+
+    fn lower(&mut self, ast: &Ast) -> Nfa {
+        match ast {
+            Ast::Char(c, _) => self.build_matcher(Matcher::Char(*c)),
+            Ast::Any(_) => self.build_matcher(Matcher::Any),
+            Ast::Class(spec, _) => self.build_matcher(Matcher::Class(spec.clone())),
+            Ast::Empty(_) => self.build_empty(),
+            // `Nfa::simulate` only ever checks whole-string acceptance, where
+            // position 0 is always start-of-text and the final position is
+            // always end-of-text, so the anchors are trivially satisfied
+            // here. Real position-sensitive enforcement lives in the VM
+            // (`vm::search`), which backs the unanchored `find`/`find_iter`.
+            Ast::StartAnchor(_) | Ast::EndAnchor(_) => self.build_empty(),
+            // Unreachable via the strict entry points (they reject any `Ast`
+            // containing an `Error` node), but kept total for callers that
+            // lower a best-effort tree from `parser::parse` directly.
+            Ast::Error(_) => self.build_empty(),
+            Ast::Concat(parts, _) => {
+                let mut iter = parts.iter();
+                let mut nfa = self.lower(iter.next().expect("Concat is never empty"));
+                for part in iter {
+                    let rhs = self.lower(part);
+                    nfa = self.build_concat(nfa, rhs);
+                }
+                nfa
+            }
+            Ast::Alt(left, right, _) => {
+                let left = self.lower(left);
+                let right = self.lower(right);
+                self.build_alt(left, right)
+            }
+            Ast::Star(inner, _) => {
+                let inner = self.lower(inner);
+                self.build_star(inner)
+            }
+            Ast::Group(inner, _, _) => self.lower(inner),
+        }
+    }
+
+    fn build_matcher(&mut self, matcher: Matcher) -> Nfa {
+        let s = self.fresh();
+        let a = self.fresh();
+        let mut nfa = Nfa::new(s, a, Transitions::new());
+        nfa.add_transition(s, Some(matcher), a);
+        nfa
+    }
+
+    fn build_empty(&mut self) -> Nfa {
+        let s = self.fresh();
+        let a = self.fresh();
+        let mut nfa = Nfa::new(s, a, Transitions::new());
+        nfa.add_transition(s, None, a);
+        nfa
+    }
+
+    fn build_concat(&mut self, left: Nfa, right: Nfa) -> Nfa {
+        let mut transitions = left.transitions;
+        transitions.extend(right.transitions);
+        let mut nfa = Nfa::new(left.start, right.accept, transitions);
+        nfa.add_transition(left.accept, None, right.start);
+        nfa
+    }
+
+    fn build_alt(&mut self, left: Nfa, right: Nfa) -> Nfa {
+        let s = self.fresh();
+        let a = self.fresh();
+        let mut transitions = left.transitions;
+        transitions.extend(right.transitions);
+        let mut nfa = Nfa::new(s, a, transitions);
+        nfa.add_transition(s, None, left.start);
+        nfa.add_transition(s, None, right.start);
+        nfa.add_transition(left.accept, None, a);
+        nfa.add_transition(right.accept, None, a);
+        nfa
+    }
+
+    fn build_star(&mut self, inner: Nfa) -> Nfa {
+        let s = self.fresh();
+        let a = self.fresh();
+        let mut nfa = Nfa::new(s, a, inner.transitions);
+        nfa.add_transition(s, None, inner.start);
+        nfa.add_transition(s, None, a);
+        nfa.add_transition(inner.accept, None, inner.start);
+        nfa.add_transition(inner.accept, None, a);
+        nfa
+    }
+}
+
+pub fn ast_to_nfa(ast: &Ast) -> Nfa {
+    NfaBuilder::new().lower(ast)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    fn nfa_for(pattern: &str) -> Nfa {
+        let (ast, errors) = parser::parse(pattern);
+        assert!(errors.is_empty(), "unexpected errors for {pattern:?}: {errors:?}");
+        ast_to_nfa(&ast.unwrap())
+    }
+
+    #[test]
+    fn matches_literal_concat() {
+        let nfa = nfa_for("abc");
+        assert!(nfa.simulate("abc"));
+        assert!(!nfa.simulate("ab"));
+        assert!(!nfa.simulate("abcd"));
+    }
+
+    #[test]
+    fn matches_alternation() {
+        let nfa = nfa_for("cat|dog");
+        assert!(nfa.simulate("cat"));
+        assert!(nfa.simulate("dog"));
+        assert!(!nfa.simulate("cow"));
+    }
+
+    #[test]
+    fn matches_star_including_empty() {
+        let nfa = nfa_for("a*");
+        assert!(nfa.simulate(""));
+        assert!(nfa.simulate("aaaa"));
+        assert!(!nfa.simulate("aaab"));
+    }
+
+    #[test]
+    fn star_of_empty_match_does_not_loop_forever() {
+        // `(a?)*` has a body that can match the empty string; without
+        // `eps_closure` skipping states already in the closure this would spin.
+        let nfa = nfa_for("(a?)*");
+        assert!(nfa.simulate(""));
+        assert!(nfa.simulate("aaa"));
+    }
+}
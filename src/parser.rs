@@ -0,0 +1,590 @@
+use crate::ast::{Ast, ClassItem, ClassSpec, ParseError, Span};
+
+type Label = Option<char>;
+
+/// Parses `pattern` to completion, recovering from problems instead of
+/// bailing on the first one: a missing `)` is synthesized, and stray tokens
+/// become `Ast::Error` placeholders so the rest of the pattern still parses.
+/// The returned `Ast` is always `Some` (a best-effort tree); callers that
+/// want strict all-or-nothing parsing should treat a non-empty error list as
+/// failure (see `regex_to_ast`).
+pub fn parse(pattern: &str) -> (Option<Ast>, Vec<ParseError>) {
+    let mut p = Parser::new(pattern);
+    let ast = p.parse_regex();
+    let ast = p.consume_trailing(ast);
+    (Some(ast), p.errors)
+}
+
+struct Parser<'a> {
+    s: &'a str, // pattern
+    i: usize,   // index
+    next_group: usize,
+    errors: Vec<ParseError>,
+    /// Counts down as `{m,n}` repetitions are desugared into copies of their
+    /// body (see `MAX_REPETITION_EXPANSION`).
+    expansion_budget: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            s: input,
+            i: 0,
+            next_group: 1,
+            errors: Vec::new(),
+            expansion_budget: MAX_REPETITION_EXPANSION,
+        }
+    }
+
+    fn error(&mut self, span: Span, message: impl Into<String>) {
+        self.errors.push(ParseError::new(span, message));
+    }
+
+    fn peek(&self) -> Label {
+        self.s[self.i..].chars().next()
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.consume();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn consume(&mut self) -> Label {
+        let c = self.peek()?;
+        self.i += c.len_utf8();
+        Some(c)
+    }
+
+    /// Anything left over once the grammar is done is reported one token at
+    /// a time and folded in as `Ast::Error` placeholders, so e.g. `a)b`
+    /// still yields a tree covering the whole pattern.
+    fn consume_trailing(&mut self, ast: Ast) -> Ast {
+        if self.peek().is_none() {
+            return ast;
+        }
+        let start = self.i;
+        let mut parts = vec![ast];
+        while self.peek().is_some() {
+            let piece_start = self.i;
+            self.consume();
+            self.error(
+                Span::new(piece_start, self.i),
+                "Unexpected trailing characters",
+            );
+            parts.push(Ast::Error(Span::new(piece_start, self.i)));
+        }
+        Ast::Concat(parts, Span::new(start, self.i))
+    }
+
+    fn parse_regex(&mut self) -> Ast {
+        self.parse_alt()
+    }
+
+    fn parse_alt(&mut self) -> Ast {
+        let start = self.i;
+        let mut left = self.parse_concat();
+        while self.eat('|') {
+            let right = self.parse_concat();
+            left = Ast::Alt(Box::new(left), Box::new(right), Span::new(start, self.i));
+        }
+        left
+    }
+
+    fn parse_concat(&mut self) -> Ast {
+        let start = self.i;
+        let mut parts = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if c == ')' || c == '|' {
+                break;
+            }
+            parts.push(self.parse_rep());
+        }
+
+        if parts.is_empty() {
+            self.error(Span::new(start, start), "Expected expression");
+            return Ast::Error(Span::new(start, start));
+        }
+        if parts.len() == 1 {
+            return parts.pop().unwrap();
+        }
+        Ast::Concat(parts, Span::new(start, self.i))
+    }
+
+    fn parse_rep(&mut self) -> Ast {
+        let start = self.i;
+        let mut ast = self.parse_primary();
+        if self.eat('*') {
+            ast = Ast::Star(Box::new(ast), Span::new(start, self.i));
+        } else if self.eat('+') {
+            // x+ desugars to x . x*
+            let star = Ast::Star(Box::new(ast.clone()), Span::new(start, self.i));
+            ast = Ast::Concat(vec![ast, star], Span::new(start, self.i));
+        } else if self.eat('?') {
+            // x? desugars to x | <empty>
+            let empty = Ast::Empty(Span::new(self.i, self.i));
+            ast = Ast::Alt(Box::new(ast), Box::new(empty), Span::new(start, self.i));
+        } else if self.peek() == Some('{') {
+            ast = self.parse_counted(ast, start);
+        }
+        ast
+    }
+
+    /// Parses `{m}`, `{m,}`, `{m,n}` and desugars onto `ast`, which must
+    /// already be fully parsed. States can't be shared between NFA copies,
+    /// so repetition is built by cloning `ast`'s tree `m`/`n` times rather
+    /// than reusing a single lowered `Nfa`.
+    fn parse_counted(&mut self, ast: Ast, start: usize) -> Ast {
+        self.consume(); // '{'
+        let m = match self.parse_count(start) {
+            Some(m) => m,
+            None => return ast, // already diagnosed; leave the atom unquantified
+        };
+
+        let n = if self.eat(',') {
+            if self.peek() == Some('}') {
+                None // {m,}
+            } else {
+                match self.parse_count(start) {
+                    Some(n) => Some(n),
+                    None => return ast,
+                }
+            }
+        } else {
+            Some(m) // {m}
+        };
+
+        if !self.eat('}') {
+            self.error(Span::new(start, self.i), "Expected '}'");
+        }
+
+        let n = n.map(|n| {
+            if n < m {
+                self.error(
+                    Span::new(start, self.i),
+                    "Invalid repetition: max is less than min",
+                );
+                m
+            } else {
+                n
+            }
+        });
+
+        // The multiplicative danger isn't any single `{m,n}` — it's nesting
+        // (`(a{1000}){1000}`), where each level multiplies the node count of
+        // the level below it. So in addition to capping `m`/`n` themselves
+        // (in `parse_count`), charge this expansion's full cost (copies of
+        // `ast`, which may itself already be a prior expansion) against a
+        // budget shared by the whole pattern.
+        let copies = m + n.map_or(1, |n| n - m);
+        let cost = ast_size(&ast).saturating_mul(copies);
+        if cost > self.expansion_budget {
+            self.error(
+                Span::new(start, self.i),
+                "Repetition would make the pattern too large to compile",
+            );
+            return ast;
+        }
+        self.expansion_budget -= cost;
+
+        build_counted(ast, m, n, Span::new(start, self.i))
+    }
+
+    fn parse_count(&mut self, start: usize) -> Option<usize> {
+        let digits_start = self.i;
+        let mut digits = String::new();
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(c);
+            self.consume();
+        }
+        if digits.is_empty() {
+            self.error(Span::new(start, self.i), "Expected a number");
+            return None;
+        }
+        match digits.parse::<usize>() {
+            Ok(n) if n > MAX_REPETITION_COUNT => {
+                self.error(
+                    Span::new(digits_start, self.i),
+                    format!("Repetition count is too large (max {MAX_REPETITION_COUNT})"),
+                );
+                None
+            }
+            Ok(n) => Some(n),
+            Err(_) => {
+                self.error(Span::new(digits_start, self.i), "Number too large");
+                None
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> Ast {
+        let start = self.i;
+        match self.peek() {
+            Some('(') => {
+                self.consume();
+                let group = self.next_group;
+                self.next_group += 1;
+                let inner = self.parse_regex();
+                if !self.eat(')') {
+                    // Synthesize the missing ')' at the current position and
+                    // keep going rather than losing the rest of the pattern.
+                    self.error(Span::new(start, self.i), "Expected ')'");
+                }
+                Ast::Group(Box::new(inner), group, Span::new(start, self.i))
+            }
+            Some('.') => {
+                self.consume();
+                Ast::Any(Span::new(start, self.i))
+            }
+            Some('^') => {
+                self.consume();
+                Ast::StartAnchor(Span::new(start, self.i))
+            }
+            Some('$') => {
+                self.consume();
+                Ast::EndAnchor(Span::new(start, self.i))
+            }
+            Some('[') => self.parse_class(start),
+            Some('\\') => {
+                self.consume();
+                match self.consume() {
+                    Some(c) => Ast::Char(c, Span::new(start, self.i)),
+                    None => {
+                        self.error(
+                            Span::new(start, self.i),
+                            "Expected a character after '\\'",
+                        );
+                        Ast::Error(Span::new(start, self.i))
+                    }
+                }
+            }
+            Some('*') | Some('+') | Some('?') | Some('|') | Some(')') => {
+                // A postfix/alternation/closing token with no atom to apply
+                // to: diagnose, skip past it, and stand in with a placeholder
+                // so the surrounding concat/alt can still be built.
+                self.consume();
+                self.error(Span::new(start, self.i), "Unexpected token");
+                Ast::Error(Span::new(start, self.i))
+            }
+            None => {
+                self.error(Span::new(start, start), "Unexpected end of pattern");
+                Ast::Error(Span::new(start, start))
+            }
+            Some(c) => {
+                self.consume();
+                Ast::Char(c, Span::new(start, self.i))
+            }
+        }
+    }
+
+    fn parse_class(&mut self, start: usize) -> Ast {
+        self.consume(); // '['
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+
+        loop {
+            match self.peek() {
+                None => {
+                    self.error(Span::new(start, self.i), "Expected ']'");
+                    break;
+                }
+                Some(']') => {
+                    self.consume();
+                    break;
+                }
+                Some(_) => {
+                    let lo = match self.consume_class_char(start) {
+                        Some(c) => c,
+                        None => break,
+                    };
+                    if self.peek() == Some('-') {
+                        let dash = self.i;
+                        self.consume();
+                        if self.peek() == Some(']') {
+                            // trailing '-' before ']' is a literal dash, not a range
+                            self.i = dash;
+                            items.push(ClassItem::Char(lo));
+                        } else {
+                            match self.consume_class_char(start) {
+                                Some(hi) => items.push(ClassItem::Range(lo, hi)),
+                                None => break,
+                            }
+                        }
+                    } else {
+                        items.push(ClassItem::Char(lo));
+                    }
+                }
+            }
+        }
+
+        if items.is_empty() {
+            self.error(Span::new(start, self.i), "Empty character class");
+            return Ast::Error(Span::new(start, self.i));
+        }
+
+        Ast::Class(ClassSpec { negated, items }, Span::new(start, self.i))
+    }
+
+    fn consume_class_char(&mut self, start: usize) -> Option<char> {
+        if self.peek() == Some('\\') {
+            self.consume();
+        }
+        match self.consume() {
+            Some(c) => Some(c),
+            None => {
+                self.error(Span::new(start, self.i), "Expected ']'");
+                None
+            }
+        }
+    }
+}
+
+/// Per-`{m,n}` cap on `m`/`n` themselves, analogous to `Ast::check_class_ranges`'s
+/// `MAX_CLASS_RANGE` for character classes.
+const MAX_REPETITION_COUNT: usize = 1000;
+
+/// Total AST-node budget that all of a pattern's `{m,n}` expansions may
+/// spend together. Unlike `MAX_REPETITION_COUNT`, this also catches nesting
+/// (`(a{1000}){1000}`), where the cost of each level multiplies rather than
+/// adds.
+const MAX_REPETITION_EXPANSION: usize = 10_000;
+
+/// Counts the nodes in `ast`, used to price a `{m,n}` expansion against
+/// `Parser::expansion_budget`.
+fn ast_size(ast: &Ast) -> usize {
+    match ast {
+        Ast::Char(..)
+        | Ast::Any(_)
+        | Ast::Class(..)
+        | Ast::Empty(_)
+        | Ast::StartAnchor(_)
+        | Ast::EndAnchor(_)
+        | Ast::Error(_) => 1,
+        Ast::Concat(parts, _) => 1 + parts.iter().map(ast_size).sum::<usize>(),
+        Ast::Alt(l, r, _) => 1 + ast_size(l) + ast_size(r),
+        Ast::Star(inner, _) => 1 + ast_size(inner),
+        Ast::Group(inner, _, _) => 1 + ast_size(inner),
+    }
+}
+
+/// Desugars `ast{m,n}` into `m` required copies followed by `n - m` optional
+/// ones (or a trailing `*` when `n` is unbounded).
+fn build_counted(ast: Ast, m: usize, n: Option<usize>, span: Span) -> Ast {
+    let mut parts = Vec::new();
+    for _ in 0..m {
+        parts.push(ast.clone());
+    }
+
+    match n {
+        None => parts.push(Ast::Star(Box::new(ast.clone()), span)),
+        Some(n) => {
+            for _ in 0..(n - m) {
+                let opt = Ast::Alt(Box::new(ast.clone()), Box::new(Ast::Empty(span)), span);
+                parts.push(opt);
+            }
+        }
+    }
+
+    match parts.len() {
+        0 => Ast::Empty(span),
+        1 => parts.pop().unwrap(),
+        _ => Ast::Concat(parts, span),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(pattern: &str) -> Ast {
+        let (ast, errors) = parse(pattern);
+        assert!(errors.is_empty(), "unexpected errors for {pattern:?}: {errors:?}");
+        ast.unwrap()
+    }
+
+    #[test]
+    fn single_char() {
+        assert!(matches!(parse_ok("a"), Ast::Char('a', _)));
+    }
+
+    #[test]
+    fn concat_of_chars() {
+        match parse_ok("abc") {
+            Ast::Concat(parts, _) => assert_eq!(parts.len(), 3),
+            other => panic!("expected Concat, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn alternation() {
+        match parse_ok("a|b") {
+            Ast::Alt(l, r, _) => {
+                assert!(matches!(*l, Ast::Char('a', _)));
+                assert!(matches!(*r, Ast::Char('b', _)));
+            }
+            other => panic!("expected Alt, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn star() {
+        match parse_ok("a*") {
+            Ast::Star(inner, _) => assert!(matches!(*inner, Ast::Char('a', _))),
+            other => panic!("expected Star, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn groups_number_left_to_right() {
+        match parse_ok("(a)(b)") {
+            Ast::Concat(parts, _) => {
+                assert!(matches!(&parts[0], Ast::Group(_, 1, _)));
+                assert!(matches!(&parts[1], Ast::Group(_, 2, _)));
+            }
+            other => panic!("expected Concat, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn plus_desugars_to_concat_with_star() {
+        match parse_ok("a+") {
+            Ast::Concat(parts, _) => {
+                assert!(matches!(&parts[0], Ast::Char('a', _)));
+                assert!(matches!(&parts[1], Ast::Star(inner, _) if matches!(**inner, Ast::Char('a', _))));
+            }
+            other => panic!("expected Concat, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn question_mark_desugars_to_alt_with_empty() {
+        match parse_ok("a?") {
+            Ast::Alt(l, r, _) => {
+                assert!(matches!(*l, Ast::Char('a', _)));
+                assert!(matches!(*r, Ast::Empty(_)));
+            }
+            other => panic!("expected Alt, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn wildcard() {
+        assert!(matches!(parse_ok("."), Ast::Any(_)));
+    }
+
+    #[test]
+    fn char_class_and_escape() {
+        match parse_ok("[a-z\\]]") {
+            Ast::Class(spec, _) => {
+                assert!(!spec.negated);
+                assert!(spec.matches('m'));
+                assert!(spec.matches(']'));
+                assert!(!spec.matches('0'));
+            }
+            other => panic!("expected Class, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn negated_char_class() {
+        match parse_ok("[^0-9]") {
+            Ast::Class(spec, _) => {
+                assert!(spec.negated);
+                assert!(!spec.matches('5'));
+                assert!(spec.matches('a'));
+            }
+            other => panic!("expected Class, got {other:?}"),
+        }
+    }
+
+    fn nfa_matches(pattern: &str, input: &str) -> bool {
+        crate::nfa::ast_to_nfa(&parse_ok(pattern)).simulate(input)
+    }
+
+    #[test]
+    fn counted_repetition_exact() {
+        assert!(nfa_matches("a{3}", "aaa"));
+        assert!(!nfa_matches("a{3}", "aa"));
+        assert!(!nfa_matches("a{3}", "aaaa"));
+    }
+
+    #[test]
+    fn counted_repetition_range() {
+        assert!(!nfa_matches("a{2,4}", "a"));
+        assert!(nfa_matches("a{2,4}", "aa"));
+        assert!(nfa_matches("a{2,4}", "aaaa"));
+        assert!(!nfa_matches("a{2,4}", "aaaaa"));
+    }
+
+    #[test]
+    fn counted_repetition_zero_zero_matches_only_empty() {
+        assert!(nfa_matches("a{0,0}", ""));
+        assert!(!nfa_matches("a{0,0}", "a"));
+    }
+
+    #[test]
+    fn counted_repetition_unbounded() {
+        assert!(nfa_matches("a{2,}", "aa"));
+        assert!(nfa_matches("a{2,}", "aaaaaa"));
+        assert!(!nfa_matches("a{2,}", "a"));
+    }
+
+    #[test]
+    fn counted_repetition_rejects_max_less_than_min() {
+        let (ast, errors) = parse("a{4,2}");
+        assert!(ast.is_some());
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn counted_repetition_rejects_count_above_cap() {
+        let (ast, errors) = parse("a{1000000}");
+        assert!(ast.is_some());
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn counted_repetition_rejects_nested_expansion_blowup() {
+        // No single `{m,n}` here exceeds MAX_REPETITION_COUNT, but nesting
+        // multiplies the expansions together into a huge tree.
+        let (ast, errors) = parse("(a{1000}){1000}");
+        assert!(ast.is_some());
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn recovers_from_missing_close_paren() {
+        let (ast, errors) = parse("a(b");
+        assert!(ast.is_some());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn recovers_from_stray_closing_paren() {
+        let (ast, errors) = parse("a)");
+        assert!(ast.is_some());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn recovery_reports_every_problem_in_a_multi_error_pattern() {
+        // Two independent stray trailing tokens after "a" (`)` and `b`).
+        // Recovery should surface both as separate diagnostics, not just one.
+        let (ast, errors) = parse("a)b");
+        assert!(ast.is_some());
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn regex_to_ast_rejects_any_recovered_error() {
+        assert!(crate::regex_to_ast("a(b").is_err());
+        assert!(crate::regex_to_ast("a").is_ok());
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::HashSet;
+
+use crate::ast::Ast;
+use crate::nfa::Matcher;
+
+/// A single bytecode instruction for the Pike VM. Compiling to this (rather
+/// than running over the `Ast` or `Nfa` directly) is what lets the thread
+/// simulation track per-thread capture slots cheaply via a plain program
+/// counter.
+#[derive(Debug, Clone)]
+enum Instr {
+    Char(Matcher),
+    Split(usize, usize),
+    Jmp(usize),
+    Save(usize),
+    /// `^`: only passable when the current position is the start of the text.
+    AssertStart,
+    /// `$`: only passable when the current position is the end of the text.
+    AssertEnd,
+    Match,
+}
+
+struct Program {
+    instrs: Vec<Instr>,
+    num_slots: usize,
+}
+
+struct Compiler {
+    instrs: Vec<Instr>,
+}
+
+impl Compiler {
+    fn new() -> Self {
+        Self { instrs: Vec::new() }
+    }
+
+    fn emit(&mut self, instr: Instr) -> usize {
+        self.instrs.push(instr);
+        self.instrs.len() - 1
+    }
+
+    fn pc(&self) -> usize {
+        self.instrs.len()
+    }
+
+    fn compile(&mut self, ast: &Ast) {
+        match ast {
+            Ast::Char(c, _) => {
+                self.emit(Instr::Char(Matcher::Char(*c)));
+            }
+            Ast::Any(_) => {
+                self.emit(Instr::Char(Matcher::Any));
+            }
+            Ast::Class(spec, _) => {
+                self.emit(Instr::Char(Matcher::Class(spec.clone())));
+            }
+            Ast::Empty(_) => {}
+            Ast::StartAnchor(_) => {
+                self.emit(Instr::AssertStart);
+            }
+            Ast::EndAnchor(_) => {
+                self.emit(Instr::AssertEnd);
+            }
+            Ast::Error(_) => {}
+            Ast::Concat(parts, _) => {
+                for part in parts {
+                    self.compile(part);
+                }
+            }
+            Ast::Alt(left, right, _) => {
+                let split = self.emit(Instr::Split(0, 0));
+                let left_pc = self.pc();
+                self.compile(left);
+                let jmp = self.emit(Instr::Jmp(0));
+                let right_pc = self.pc();
+                self.compile(right);
+                let end_pc = self.pc();
+                self.instrs[split] = Instr::Split(left_pc, right_pc);
+                self.instrs[jmp] = Instr::Jmp(end_pc);
+            }
+            Ast::Star(inner, _) => {
+                let split_at = self.pc();
+                let split = self.emit(Instr::Split(0, 0));
+                let body_pc = self.pc();
+                self.compile(inner);
+                self.emit(Instr::Jmp(split_at));
+                let end_pc = self.pc();
+                self.instrs[split] = Instr::Split(body_pc, end_pc);
+            }
+            Ast::Group(inner, idx, _) => {
+                self.emit(Instr::Save(2 * idx));
+                self.compile(inner);
+                self.emit(Instr::Save(2 * idx + 1));
+            }
+        }
+    }
+}
+
+fn compile(ast: &Ast) -> Program {
+    let num_groups = ast.max_group();
+    let mut compiler = Compiler::new();
+    compiler.emit(Instr::Save(0));
+    compiler.compile(ast);
+    compiler.emit(Instr::Save(1));
+    compiler.emit(Instr::Match);
+    Program {
+        instrs: compiler.instrs,
+        num_slots: 2 * (num_groups + 1),
+    }
+}
+
+type Slots = Vec<Option<usize>>;
+
+#[derive(Clone)]
+struct Thread {
+    pc: usize,
+    slots: Slots,
+}
+
+/// Follows epsilon-instructions (`Split`/`Jmp`/`Save`/asserts) immediately,
+/// queueing a thread once it reaches a `Char` or `Match`. `visited` guards
+/// against an empty-matching `*` looping forever by never re-adding the same
+/// `pc` at the same input position. `end` is the byte length of the whole
+/// text, so `$` can tell a mid-string position from true end-of-text.
+#[allow(clippy::too_many_arguments)]
+fn add_thread(
+    list: &mut Vec<Thread>,
+    visited: &mut HashSet<usize>,
+    prog: &[Instr],
+    pc: usize,
+    sp: usize,
+    end: usize,
+    slots: Slots,
+) {
+    if !visited.insert(pc) {
+        return;
+    }
+    match &prog[pc] {
+        Instr::Jmp(target) => add_thread(list, visited, prog, *target, sp, end, slots),
+        Instr::Split(a, b) => {
+            add_thread(list, visited, prog, *a, sp, end, slots.clone());
+            add_thread(list, visited, prog, *b, sp, end, slots);
+        }
+        Instr::Save(slot) => {
+            let mut slots = slots;
+            slots[*slot] = Some(sp);
+            add_thread(list, visited, prog, pc + 1, sp, end, slots);
+        }
+        Instr::AssertStart => {
+            if sp == 0 {
+                add_thread(list, visited, prog, pc + 1, sp, end, slots);
+            }
+        }
+        Instr::AssertEnd => {
+            if sp == end {
+                add_thread(list, visited, prog, pc + 1, sp, end, slots);
+            }
+        }
+        Instr::Char(_) | Instr::Match => {
+            list.push(Thread { pc, slots });
+        }
+    }
+}
+
+/// Runs the thread-list simulation to completion over `input`, returning the
+/// winning thread's slots. Threads are processed in priority order, so the
+/// first `Match` reached once the whole input is consumed beats (and
+/// discards) every lower-priority thread still in the list, giving greedy
+/// semantics for the surrounding `*`/`|`.
+fn run(prog: &Program, input: &str) -> Option<Slots> {
+    let end = input.len();
+    let mut clist = Vec::new();
+    let mut visited = HashSet::new();
+    add_thread(&mut clist, &mut visited, &prog.instrs, 0, 0, end, vec![None; prog.num_slots]);
+
+    let mut matched = None;
+    let mut chars = input.char_indices().peekable();
+
+    loop {
+        let (sp, c) = match chars.peek() {
+            Some(&(sp, c)) => (sp, Some(c)),
+            None => (end, None),
+        };
+
+        let mut nlist = Vec::new();
+        let mut nvisited = HashSet::new();
+
+        for thread in clist {
+            match &prog.instrs[thread.pc] {
+                Instr::Char(matcher) => {
+                    if let Some(c) = c {
+                        if matcher.matches(c) {
+                            let next_sp = sp + c.len_utf8();
+                            add_thread(&mut nlist, &mut nvisited, &prog.instrs, thread.pc + 1, next_sp, end, thread.slots);
+                        }
+                    }
+                }
+                Instr::Match => {
+                    // Matching the whole pattern is only a real match once the
+                    // whole input has been consumed; a thread hitting `Match`
+                    // earlier just dies without discarding its lower-priority
+                    // siblings, since a fuller match may still come from them.
+                    if sp == end {
+                        matched = Some(thread.slots);
+                        break;
+                    }
+                }
+                _ => unreachable!("add_thread only queues Char/Match instructions"),
+            }
+        }
+
+        clist = nlist;
+        if c.is_none() || clist.is_empty() {
+            break;
+        }
+        chars.next();
+    }
+
+    matched
+}
+
+/// Unanchored search for the leftmost match starting at or after byte offset
+/// `from`. A fresh thread starting the whole program over is injected at
+/// every position (lowest priority, so earlier starts always win), which is
+/// the standard way to turn an anchored VM into `find`'s "match anywhere"
+/// search without literally prefixing the program with a `.*?`.
+fn search(prog: &Program, input: &str, from: usize) -> Option<Slots> {
+    let end = input.len();
+    let mut clist = Vec::new();
+    let mut visited = HashSet::new();
+    add_thread(&mut clist, &mut visited, &prog.instrs, 0, from, end, vec![None; prog.num_slots]);
+
+    let mut matched = None;
+    let mut chars = input
+        .char_indices()
+        .skip_while(|&(i, _)| i < from)
+        .peekable();
+
+    loop {
+        let (sp, c) = match chars.peek() {
+            Some(&(sp, c)) => (sp, Some(c)),
+            None => (end, None),
+        };
+
+        let mut nlist = Vec::new();
+        let mut nvisited = HashSet::new();
+
+        for thread in clist {
+            match &prog.instrs[thread.pc] {
+                Instr::Char(matcher) => {
+                    if let Some(c) = c {
+                        if matcher.matches(c) {
+                            let next_sp = sp + c.len_utf8();
+                            add_thread(&mut nlist, &mut nvisited, &prog.instrs, thread.pc + 1, next_sp, end, thread.slots);
+                        }
+                    }
+                }
+                Instr::Match => {
+                    // This thread would accept here, but anything still
+                    // ahead of it in `nlist` (added from higher-priority
+                    // threads processed earlier this step) is a greedier
+                    // continuation and should get the chance to extend the
+                    // match further at a later position, so don't stop the
+                    // whole search — just record this as the best match so
+                    // far and drop everything lower-priority than it.
+                    matched = Some(thread.slots.clone());
+                    break;
+                }
+                _ => unreachable!("add_thread only queues Char/Match instructions"),
+            }
+        }
+
+        // Once some lineage has matched, it's always higher-priority than a
+        // start injected from here on (later starts are strictly later in
+        // thread-list order), so there's no point keeping the unanchored
+        // search alive for new starts — only the already-matched lineage's
+        // own greedier continuations (already in `nlist`) still matter.
+        if matched.is_none() {
+            if let Some(c) = c {
+                let next_start = sp + c.len_utf8();
+                add_thread(&mut nlist, &mut nvisited, &prog.instrs, 0, next_start, end, vec![None; prog.num_slots]);
+            }
+        }
+
+        clist = nlist;
+        if c.is_none() || clist.is_empty() {
+            break;
+        }
+        chars.next();
+    }
+
+    matched
+}
+
+/// The byte span of each capture group (group 0 is the whole match), or
+/// `None` for a group that never participated (e.g. the untaken side of an
+/// `|`).
+pub type Captures = Vec<Option<(usize, usize)>>;
+
+/// Compiles `ast` and runs it against `input`, returning the byte span of
+/// each capture group if the whole input matches (group 0 is the full
+/// match). `None` if the pattern doesn't match `input` in its entirety.
+pub fn captures(ast: &Ast, input: &str) -> Option<Captures> {
+    let prog = compile(ast);
+    let slots = run(&prog, input)?;
+
+    Some(
+        slots
+            .chunks(2)
+            .map(|pair| match (pair[0], pair[1]) {
+                (Some(start), Some(end)) => Some((start, end)),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+/// Finds the leftmost match of `ast` anywhere in `input`, unlike `captures`
+/// which requires the whole input to match.
+pub fn find(ast: &Ast, input: &str) -> Option<(usize, usize)> {
+    let prog = compile(ast);
+    let slots = search(&prog, input, 0)?;
+    Some((slots[0].unwrap(), slots[1].unwrap()))
+}
+
+/// Yields successive non-overlapping leftmost matches of `ast` in `input`.
+pub struct FindIter<'a> {
+    prog: Program,
+    input: &'a str,
+    pos: usize,
+    done: bool,
+}
+
+impl<'a> Iterator for FindIter<'a> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        if self.done {
+            return None;
+        }
+        let slots = search(&self.prog, self.input, self.pos)?;
+        let (start, end) = (slots[0].unwrap(), slots[1].unwrap());
+
+        if end >= self.input.len() {
+            self.done = true;
+        }
+        self.pos = if end > start {
+            end
+        } else {
+            // Avoid looping forever on an empty match: step to the next char.
+            match self.input[end..].chars().next() {
+                Some(c) => end + c.len_utf8(),
+                None => {
+                    self.done = true;
+                    end
+                }
+            }
+        };
+
+        Some((start, end))
+    }
+}
+
+pub fn find_iter<'a>(ast: &Ast, input: &'a str) -> FindIter<'a> {
+    FindIter {
+        prog: compile(ast),
+        input,
+        pos: 0,
+        done: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ast_for(pattern: &str) -> Ast {
+        let (ast, errors) = crate::parser::parse(pattern);
+        assert!(errors.is_empty(), "unexpected errors for {pattern:?}: {errors:?}");
+        ast.unwrap()
+    }
+
+    #[test]
+    fn captures_basic_groups() {
+        let caps = captures(&ast_for("(a+)(b+)"), "aaabb").unwrap();
+        assert_eq!(caps[0], Some((0, 5)));
+        assert_eq!(caps[1], Some((0, 3)));
+        assert_eq!(caps[2], Some((3, 5)));
+    }
+
+    #[test]
+    fn captures_none_for_untaken_alt_branch() {
+        let caps = captures(&ast_for("(a)|(b)"), "b").unwrap();
+        assert_eq!(caps[0], Some((0, 1)));
+        assert_eq!(caps[1], None);
+        assert_eq!(caps[2], Some((0, 1)));
+    }
+
+    #[test]
+    fn captures_star_group_keeps_last_iteration() {
+        // Each iteration of the star re-runs Save(2)/Save(3), so the group's
+        // reported span is whichever repetition ran last, not the first.
+        let caps = captures(&ast_for("(a)*"), "aaa").unwrap();
+        assert_eq!(caps[0], Some((0, 3)));
+        assert_eq!(caps[1], Some((2, 3)));
+    }
+
+    #[test]
+    fn captures_none_when_whole_input_does_not_match() {
+        assert!(captures(&ast_for("(a)(b)"), "ab ").is_none());
+    }
+
+    #[test]
+    fn find_is_unanchored_and_leftmost() {
+        assert_eq!(find(&ast_for("b+"), "aabbbcc"), Some((2, 5)));
+        assert_eq!(find(&ast_for("x"), "abc"), None);
+    }
+
+    #[test]
+    fn find_honors_start_and_end_anchors() {
+        assert_eq!(find(&ast_for("^a"), "aabbbcc"), Some((0, 1)));
+        assert_eq!(find(&ast_for("^b"), "aabbbcc"), None);
+        assert_eq!(find(&ast_for("c$"), "aabbbcc"), Some((6, 7)));
+        assert_eq!(find(&ast_for("b$"), "aabbbcc"), None);
+    }
+
+    #[test]
+    fn find_iter_yields_every_non_overlapping_match() {
+        let matches: Vec<_> = find_iter(&ast_for("a+"), "aa bb a aaa").collect();
+        assert_eq!(matches, vec![(0, 2), (6, 7), (8, 11)]);
+    }
+
+    #[test]
+    fn find_iter_does_not_loop_forever_on_empty_matches() {
+        let matches: Vec<_> = find_iter(&ast_for("a*"), "ab").collect();
+        assert_eq!(matches, vec![(0, 1), (1, 1), (2, 2)]);
+    }
+}